@@ -2,19 +2,428 @@ use std::{
     cell::{Ref, RefCell},
     fmt::Debug,
     fs::File,
-    io::{BufReader, Error},
+    io::{self, BufReader, Error, Read, Seek, SeekFrom},
     ops::Add,
     path::Path,
     ptr::null,
-    sync::{Arc, Mutex},
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Arc, Condvar, Mutex,
+    },
     thread,
     time::{Duration, Instant},
 };
 
-use rodio::{Decoder, OutputStream, OutputStreamHandle, Sink, Source};
+use rand::seq::SliceRandom;
+use rodio::{source::Buffered, Decoder, OutputStream, OutputStreamHandle, Sink, Source};
 
 use super::media::Media;
 
+// 可随机访问的字节源，本地文件与 HTTP 流都实现它，这样解码后的 `Buffered`
+// 只有一个具体类型，便于在列表项之间 `Arc`-clone 复用。
+trait ReadSeek: Read + Seek + Send {}
+impl<T: Read + Seek + Send> ReadSeek for T {}
+
+impl Read for Box<dyn ReadSeek> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        (**self).read(buf)
+    }
+}
+
+impl Seek for Box<dyn ReadSeek> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        (**self).seek(pos)
+    }
+}
+
+// 解码后可被廉价克隆（内部 `Arc`）的音频源，seek/预加载都依赖它。
+type BufferedSource = Buffered<Decoder<Box<dyn ReadSeek>>>;
+
+// 当前曲目剩余时间小于该阈值时，提前在后台线程解码下一首，避免切歌时的解码卡顿。
+const PRELOAD_THRESHOLD: Duration = Duration::from_secs(30);
+
+// 复制一个 `Source`，用于把下一首的来源交给后台预加载线程。
+fn clone_source(src: &super::media::Source) -> super::media::Source {
+    match src {
+        super::media::Source::Http(url) => super::media::Source::Http(url.clone()),
+        super::media::Source::Local(path) => super::media::Source::Local(path.clone()),
+    }
+}
+
+// 与后端无关的时长探测：按扩展名/魔数区分容器，MP3 仍走精度更好的
+// `mp3_duration`，FLAC/WAV/OGG/M4A 等交给 symphonia 读元数据，最后兜底用
+// rodio 解码器自报的 `total_duration()`。
+fn probe_duration(path: &Path) -> Option<Duration> {
+    let ext = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|s| s.to_ascii_lowercase());
+    if ext.as_deref() == Some("mp3") {
+        if let Ok(d) = mp3_duration::from_path(path) {
+            return Some(d);
+        }
+    }
+    if let Some(d) = probe_duration_symphonia(path) {
+        return Some(d);
+    }
+    // 兜底：让 rodio 自己解码并报告总时长（对 WAV 等格式通常可用）
+    let file = File::open(path).ok()?;
+    Decoder::new(BufReader::new(file)).ok()?.total_duration()
+}
+
+// 用 symphonia 打开文件并从默认音轨的 time_base 与帧数推算时长。
+fn probe_duration_symphonia(path: &Path) -> Option<Duration> {
+    use symphonia::core::formats::FormatOptions;
+    use symphonia::core::io::MediaSourceStream;
+    use symphonia::core::meta::MetadataOptions;
+    use symphonia::core::probe::Hint;
+
+    let file = File::open(path).ok()?;
+    let mss = MediaSourceStream::new(Box::new(file), Default::default());
+    let mut hint = Hint::new();
+    if let Some(ext) = path.extension().and_then(|e| e.to_str()) {
+        hint.with_extension(ext);
+    }
+    let probed = symphonia::default::get_probe()
+        .format(
+            &hint,
+            mss,
+            &FormatOptions::default(),
+            &MetadataOptions::default(),
+        )
+        .ok()?;
+    let track = probed.format.default_track()?;
+    let params = &track.codec_params;
+    let time_base = params.time_base?;
+    let n_frames = params.n_frames?;
+    let time = time_base.calc_time(n_frames);
+    Some(Duration::from_secs(time.seconds).add(Duration::from_secs_f64(time.frac)))
+}
+
+// 打开并探测一个媒体来源，返回可随机访问的读取器、时长以及显示用的文件名。
+// 本地文件与 HTTP 流共用这条路径，方便预加载线程复用。
+// 返回的最后一个布尔表示时长是否只是估计值（HTTP 流无法精确测量，
+// 尤其是没有 Content-Length 的直播流），tick 据此改用 `Sink::empty()` 判断结束。
+fn open_media(src: &super::media::Source) -> Option<(Box<dyn ReadSeek>, Duration, String, bool)> {
+    match src {
+        super::media::Source::Http(url) => {
+            let reader = HttpStreamReader::open(url.as_str()).ok()?;
+            let duration = reader.estimate_duration().unwrap_or(Duration::from_secs(0));
+            let file_name = url
+                .rsplit('/')
+                .next()
+                .filter(|s| !s.is_empty())
+                .unwrap_or(url.as_str())
+                .to_string();
+            Some((Box::new(reader), duration, file_name, true))
+        }
+        super::media::Source::Local(path) => {
+            let p = Path::new(path.as_str());
+            let f = File::open(p).ok()?;
+            let file_name = p.file_name().unwrap().to_string_lossy().to_string();
+            // 不再因为“不是 MP3”就拒绝：只要能探测出时长就接受，
+            // rodio 的 Decoder 本身就能播放 FLAC/WAV/OGG/M4A 等格式。
+            let duration = probe_duration(p)?;
+            Some((Box::new(f), duration, file_name, false))
+        }
+    }
+}
+
+// 每次向远端请求的字节块大小
+const HTTP_CHUNK_SIZE: u64 = 64 * 1024;
+// 对没有 Content-Length 的无限流（直播/ICY）缓存的上限，避免无界增长
+const HTTP_STREAM_CAP: u64 = 8 * 1024 * 1024;
+// TCP 连接超时：握手阶段卡住时尽快失败，而不是悬在这里
+const HTTP_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+// 单次请求超时：服务器接受了连接但一直不回应时，这条 Range 请求按超时失败，
+// 而不是让 `open()`/`tick()` 所在的播放线程永久卡死
+const HTTP_REQUEST_TIMEOUT: Duration = Duration::from_secs(15);
+
+// 一次 Range 请求拿到的响应：是否为部分响应（206）、总长度、比特率，以及
+// 可增量读取的 body。把网络细节收敛到这个 trait，后台线程只面向它工作，
+// 测试时也能用内存里的假实现驱动同样的填充逻辑。
+struct RangeResponse {
+    // 服务器是否以 206 Partial Content 应答（真正支持 Range）
+    partial: bool,
+    // Content-Length，未知（无限流）时为 None
+    content_length: Option<u64>,
+    // 由 `icy-br` 解析出的比特率（bit/s），没有则为 None
+    bitrate: Option<u32>,
+    // 响应体，按需增量读取，不一次性全部读进内存
+    body: Box<dyn Read + Send>,
+}
+
+trait RangeFetcher: Send {
+    // 从 `start` 偏移请求至多 `len` 字节
+    fn get(&self, start: u64, len: u64) -> io::Result<RangeResponse>;
+}
+
+// 基于 reqwest 的真实实现
+struct HttpFetcher {
+    client: reqwest::blocking::Client,
+    url: String,
+}
+
+impl RangeFetcher for HttpFetcher {
+    fn get(&self, start: u64, len: u64) -> io::Result<RangeResponse> {
+        let range = format!("bytes={}-{}", start, start + len - 1);
+        let resp = self
+            .client
+            .get(&self.url)
+            .header(reqwest::header::RANGE, range)
+            .send()
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+        let partial = resp.status() == reqwest::StatusCode::PARTIAL_CONTENT;
+        let content_length = resp
+            .headers()
+            .get(reqwest::header::CONTENT_LENGTH)
+            // 206 的 Content-Length 只是本段长度，整段长度要从 Content-Range 取；
+            // 这里只在非部分响应时把它当作整体长度使用。
+            .filter(|_| !partial)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse::<u64>().ok());
+        // ICY 直播流会带 `icy-br`（kbps）
+        let bitrate = resp
+            .headers()
+            .get("icy-br")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.split(',').next())
+            .and_then(|v| v.trim().parse::<u32>().ok())
+            .map(|kbps| kbps * 1000);
+        Ok(RangeResponse {
+            partial,
+            content_length,
+            bitrate,
+            body: Box::new(resp),
+        })
+    }
+}
+
+// 后台预取线程与前台读取线程共享的缓冲区状态
+#[derive(Default)]
+struct StreamState {
+    // 已经下载并缓存的字节，按偏移顺序追加
+    buffer: Vec<u8>,
+    // 远端资源总长度（来自 Content-Length），未知时为 None
+    total_len: Option<u64>,
+    // 平均比特率（bit/s），用于在无法精确得到时长时做估算
+    bitrate: Option<u32>,
+    // 首个响应的头部已解析（total_len/bitrate 可用）
+    headers_ready: bool,
+    // 不会再有新数据（整段读完、读到 EOF 或触达无限流上限）
+    complete: bool,
+    // 预取过程中发生过错误
+    errored: bool,
+}
+
+// 后台预取循环：不断向远端索取数据填充共享 buffer，前台 `read` 只消费已缓存
+// 的字节、绝不在音频线程上发起网络 IO。独立成自由函数以便测试直接驱动。
+fn prefetch(shared: &Arc<(Mutex<StreamState>, Condvar)>, fetcher: &dyn RangeFetcher, cap: u64) {
+    let (lock, cvar) = &**shared;
+    let mut start = 0u64;
+    let mut first = true;
+    loop {
+        let resp = match fetcher.get(start, HTTP_CHUNK_SIZE) {
+            Ok(r) => r,
+            Err(_) => {
+                let mut s = lock.lock().unwrap();
+                s.errored = true;
+                s.headers_ready = true;
+                s.complete = true;
+                cvar.notify_all();
+                return;
+            }
+        };
+        if first {
+            let mut s = lock.lock().unwrap();
+            s.total_len = resp.content_length;
+            s.bitrate = resp.bitrate;
+            s.headers_ready = true;
+            cvar.notify_all();
+            first = false;
+        }
+
+        if !resp.partial {
+            // 服务器忽略了 Range，这条响应就是从 0 开始的整段 body。
+            // 之前若缓存过 Range 数据就丢弃重来（正常只会在首个请求命中这里）。
+            {
+                let mut s = lock.lock().unwrap();
+                s.buffer.clear();
+            }
+            let mut body = resp.body;
+            let mut chunk = [0u8; 8192];
+            loop {
+                // 对无限流（无 Content-Length）设上限，避免把直播流无界地吞进内存
+                {
+                    let s = lock.lock().unwrap();
+                    let reached_cap = s.total_len.is_none() && s.buffer.len() as u64 >= cap;
+                    let reached_end = matches!(s.total_len, Some(t) if s.buffer.len() as u64 >= t);
+                    if reached_cap || reached_end {
+                        break;
+                    }
+                }
+                match body.read(&mut chunk) {
+                    Ok(0) => break,
+                    Ok(n) => {
+                        let mut s = lock.lock().unwrap();
+                        s.buffer.extend_from_slice(&chunk[..n]);
+                        cvar.notify_all();
+                    }
+                    Err(_) => {
+                        lock.lock().unwrap().errored = true;
+                        break;
+                    }
+                }
+            }
+            let mut s = lock.lock().unwrap();
+            s.complete = true;
+            cvar.notify_all();
+            return;
+        }
+
+        // 206：body 就是本段（长度有界），读完后追加，再请求下一段
+        let mut chunk = Vec::new();
+        if resp.body.take(HTTP_CHUNK_SIZE).read_to_end(&mut chunk).is_err() {
+            let mut s = lock.lock().unwrap();
+            s.errored = true;
+            s.complete = true;
+            cvar.notify_all();
+            return;
+        }
+        if chunk.is_empty() {
+            let mut s = lock.lock().unwrap();
+            s.complete = true;
+            cvar.notify_all();
+            return;
+        }
+        let done = {
+            let mut s = lock.lock().unwrap();
+            s.buffer.extend_from_slice(&chunk);
+            start = s.buffer.len() as u64;
+            cvar.notify_all();
+            matches!(s.total_len, Some(t) if s.buffer.len() as u64 >= t)
+        };
+        if done {
+            let mut s = lock.lock().unwrap();
+            s.complete = true;
+            cvar.notify_all();
+            return;
+        }
+    }
+}
+
+// 针对 HTTP 远端资源的 `Read + Seek` 适配器。
+// rodio 的 `Decoder` 需要一个可随机访问的读取器，而我们又不想在播放前把
+// 整个文件下载下来，所以由后台线程按 Range 增量预取、填充共享 buffer；
+// 本结构只持有读游标，`read` 等待预取线程把数据送到，绝不自己发网络请求。
+struct HttpStreamReader {
+    shared: Arc<(Mutex<StreamState>, Condvar)>,
+    // 当前读游标
+    pos: u64,
+}
+
+impl HttpStreamReader {
+    fn open(url: &str) -> io::Result<Self> {
+        // 不设超时的话，服务器只接受连接却一直不回应会把 open()/tick() 所在的
+        // 播放线程永久挂起，不止卡住这一首，整个播放器都会被冻结。
+        let client = reqwest::blocking::Client::builder()
+            .connect_timeout(HTTP_CONNECT_TIMEOUT)
+            .timeout(HTTP_REQUEST_TIMEOUT)
+            .build()
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+        let fetcher = HttpFetcher {
+            client,
+            url: url.to_string(),
+        };
+        let reader = Self::with_fetcher(Box::new(fetcher), HTTP_STREAM_CAP);
+        // 等首个响应头部就绪（total_len/bitrate），以便估算时长；只等头部、
+        // 不等 body，所以即便是无限直播流也能很快返回。
+        let (lock, cvar) = &*reader.shared;
+        let mut s = lock.lock().unwrap();
+        while !s.headers_ready {
+            s = cvar.wait(s).unwrap();
+        }
+        if s.errored && s.buffer.is_empty() && s.total_len.is_none() {
+            return Err(io::Error::new(io::ErrorKind::Other, "failed to open stream"));
+        }
+        drop(s);
+        Ok(reader)
+    }
+
+    // 装配一个由给定 fetcher 驱动的读取器，并启动后台预取线程。
+    fn with_fetcher(fetcher: Box<dyn RangeFetcher>, cap: u64) -> Self {
+        let shared: Arc<(Mutex<StreamState>, Condvar)> =
+            Arc::new((Mutex::new(StreamState::default()), Condvar::new()));
+        let worker = Arc::clone(&shared);
+        thread::spawn(move || prefetch(&worker, fetcher.as_ref(), cap));
+        Self { shared, pos: 0 }
+    }
+
+    // 根据已知信息估算总时长：优先用比特率，其次用 Content-Length 与
+    // 经验比特率（128kbps）反推。结果只是估计值，不随已读数据修正。
+    fn estimate_duration(&self) -> Option<Duration> {
+        let s = self.shared.0.lock().unwrap();
+        let bitrate = s.bitrate.unwrap_or(128 * 1000);
+        if bitrate == 0 {
+            return None;
+        }
+        let total = s.total_len?;
+        Some(Duration::from_secs_f64(
+            (total as f64 * 8.0) / bitrate as f64,
+        ))
+    }
+}
+
+impl Read for HttpStreamReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let (lock, cvar) = &*self.shared;
+        let mut s = lock.lock().unwrap();
+        // 等待后台线程把游标处的数据取到；本线程绝不发起网络 IO
+        loop {
+            if self.pos < s.buffer.len() as u64 {
+                let start = self.pos as usize;
+                let end = (start + buf.len()).min(s.buffer.len());
+                let n = end - start;
+                buf[..n].copy_from_slice(&s.buffer[start..end]);
+                self.pos += n as u64;
+                return Ok(n);
+            }
+            if s.complete {
+                // 没有更多数据，视为流结束
+                return Ok(0);
+            }
+            if s.errored {
+                return Err(io::Error::new(io::ErrorKind::Other, "stream prefetch failed"));
+            }
+            s = cvar.wait(s).unwrap();
+        }
+    }
+}
+
+impl Seek for HttpStreamReader {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let new_pos = match pos {
+            SeekFrom::Start(n) => n as i64,
+            SeekFrom::Current(n) => self.pos as i64 + n,
+            SeekFrom::End(n) => {
+                let total = self.shared.0.lock().unwrap().total_len.ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::Unsupported, "unknown length")
+                })?;
+                total as i64 + n
+            }
+        };
+        if new_pos < 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "seek before start",
+            ));
+        }
+        // 只移动游标，目标位置的数据由后台线程顺序填充，`read` 时再等待即可
+        self.pos = new_pos as u64;
+        Ok(self.pos)
+    }
+}
+
 #[derive(PartialEq, Eq, PartialOrd, Ord)]
 pub enum PlayStatus {
     Waiting,
@@ -23,14 +432,101 @@ pub enum PlayStatus {
 }
 
 pub struct PlayListItem {
+    // 列表内唯一 id，由 MusicPlayer 递增分配；重复播放、随机历史、预加载
+    // 取用都按它匹配，文件名可能重复（同名曲目入队两次、不同专辑的 01.mp3）。
+    pub id: u64,
     pub name: String,
     pub duration: Duration,
     pub current_pos: Duration,
     pub status: PlayStatus,
+    // 曲目来源，用于在切歌时重新解码或做后台预加载
+    pub src: super::media::Source,
+    // duration 是否只是估计值（HTTP 流）。为真时用 Sink 是否排空来判断播放结束，
+    // 而不是拿 wall-clock 去和可能为 0 的 duration 比较。
+    pub duration_estimated: bool,
+}
+
+impl PlayListItem {
+    // 当前播放头：记录的起始偏移加上本次播放已过去的时间（start_position + elapsed）。
+    pub fn playhead(&self) -> Duration {
+        match &self.status {
+            PlayStatus::Waiting => Duration::from_nanos(0),
+            PlayStatus::Playing(instant, start) => instant.elapsed().add(*start),
+            PlayStatus::Stopped(dur) => *dur,
+        }
+    }
+}
+
+// 按播放模式推断下一首将要播放的曲目下标，供预加载提前命中。随机模式下的
+// 下一首要到切歌时才随机确定，无法提前命中，故返回 None（跳过预加载）。
+fn peek_next_index_for(mode: PlayMode, len: usize) -> Option<usize> {
+    if len == 0 {
+        return None;
+    }
+    match mode {
+        // 单曲循环：下一首还是队首自己
+        PlayMode::RepeatOne => Some(0),
+        // 顺序播放：队首放完后轮到下标 1，没有后续则不预加载
+        PlayMode::Sequential => (len > 1).then_some(1),
+        // 列表循环：队首挪到队尾后新的队首是原下标 1；整张只有一首则仍是它自己
+        PlayMode::RepeatAll => Some(if len > 1 { 1 } else { 0 }),
+        // 随机模式：下一首在切歌时才确定，无法提前预取
+        PlayMode::Shuffle => None,
+    }
+}
+
+// 随机模式下尚未播放过的曲目下标，用于在重复之前放完整张列表。
+// 按 id 而非文件名匹配：同名曲目入队两次（或不同专辑里同名的 01.mp3）
+// 不应该互相当成“这首已经放过了”。
+fn unplayed_indices(lists: &[PlayListItem], played: &[u64]) -> Vec<usize> {
+    lists
+        .iter()
+        .enumerate()
+        .filter(|(_, item)| !played.contains(&item.id))
+        .map(|(i, _)| i)
+        .collect()
+}
+
+// 播放模式：顺序、单曲循环、列表循环、随机。
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PlayMode {
+    Sequential,
+    RepeatOne,
+    RepeatAll,
+    Shuffle,
+}
+
+impl PlayMode {
+    // 按固定顺序切换到下一个模式，供 TUI 循环切换使用。
+    pub fn next(self) -> Self {
+        match self {
+            PlayMode::Sequential => PlayMode::RepeatOne,
+            PlayMode::RepeatOne => PlayMode::RepeatAll,
+            PlayMode::RepeatAll => PlayMode::Shuffle,
+            PlayMode::Shuffle => PlayMode::Sequential,
+        }
+    }
+}
+
+// 播放状态变化事件，供 UI、日志、网络端等多个消费者以对等方式订阅，
+// 取代对 `tick`/`play_list`/`is_playing` 的轮询。
+#[derive(Clone, Debug, PartialEq)]
+pub enum PlayerEvent {
+    TrackStarted(String),
+    TrackEnded(String),
+    Paused,
+    Resumed,
+    VolumeChanged(f32),
+    PositionChanged(Duration),
+    PlaylistEmptied,
 }
 
 pub struct PlayList {
     pub lists: Vec<PlayListItem>,
+    // 当前播放模式
+    pub mode: PlayMode,
+    // 随机模式下已经播放过的曲目 id，用于播完整张再重来
+    played: Vec<u64>,
 }
 
 pub trait Player {
@@ -55,6 +551,9 @@ pub trait Player {
     // 继续
     fn resume(&mut self) -> bool;
 
+    // 跳转到曲目内的指定位置
+    fn seek(&mut self, pos: Duration) -> bool;
+
     // 播放进度
     fn get_progress(&self) -> (f32, f32);
 
@@ -75,7 +574,18 @@ pub struct MusicPlayer {
     _stream: OutputStream,
     _stream_handle: OutputStreamHandle,
     _sink: Sink,
+    // 当前曲目解码后的缓冲源，seek 时从它廉价克隆出新的播放流
+    current_source: Option<BufferedSource>,
+    // 提前解码好的下一首，键为其列表项 id（而非文件名，同名曲目可能重复入队）；
+    // 切歌时直接取用，避免在 tick 里同步解码
+    preload: Option<(u64, BufferedSource)>,
+    // 后台预加载线程的结果回传端
+    preload_rx: Option<Receiver<(u64, BufferedSource)>>,
+    // 已订阅状态变化的消费者
+    subscribers: Vec<Sender<PlayerEvent>>,
     initialized: bool,
+    // 下一个列表项分配的 id，严格递增，保证同名曲目也能彼此区分
+    next_item_id: u64,
 }
 
 impl Player for MusicPlayer {
@@ -85,68 +595,33 @@ impl Player for MusicPlayer {
         Self {
             current_time: Duration::from_secs(0),
             total_time: Duration::from_secs(0),
-            play_list: PlayList { lists: vec![] },
+            play_list: PlayList {
+                lists: vec![],
+                mode: PlayMode::Sequential,
+                played: vec![],
+            },
             // media: f,
             _stream: stream,
             _stream_handle: stream_handle,
             _sink: sink,
+            current_source: None,
+            preload: None,
+            preload_rx: None,
+            subscribers: vec![],
             initialized: false,
+            next_item_id: 0,
         }
     }
 
     fn add_to_list(&mut self, media: Media, once: bool) -> bool {
-        match media.src {
-            super::media::Source::Http(_) => {
-                todo!();
-            }
-            super::media::Source::Local(path) => {
-                match File::open(path.as_str()) {
-                    Ok(f) => {
-                        let path = Path::new(path.as_str());
-                        let file_name = path.file_name().unwrap().to_string_lossy().to_string();
-                        // Result<(stream,streamHanlde),std::error:Error>
-                        let mp3d = mp3_duration::from_file(&f).ok();
-                        if let Some(duration) = mp3d {
-                            let buf_reader = BufReader::new(f);
-                            match Decoder::new(buf_reader) {
-                                Ok(dec) => {
-                                    if !self.initialized {
-                                        self.initialized = true;
-                                        self.start_listening_thread();
-                                    }
-                                    if once {
-                                        self._sink.stop();
-                                        self._sink = Sink::try_new(&self._stream_handle).unwrap();
-                                        self.play_list.lists.clear();
-                                    }
-
-                                    self._sink.append(dec);
-
-                                    // add to playlist
-                                    self.play_list.lists.push(PlayListItem {
-                                        name: file_name,
-                                        duration: duration,
-                                        current_pos: Duration::from_secs(0),
-                                        status: PlayStatus::Waiting,
-                                    });
-                                    // start play
-                                    self.play();
-                                    // manually tick once
-                                    self.tick();
-                                    return true;
-                                }
-                                Err(_) => {
-                                    return false;
-                                }
-                            }
-                        } else {
-                            return false;
-                        }
-                    }
-                    Err(_) => false,
-                }
-            }
-        }
+        // HTTP 流无法对部分数据调用 mp3_duration，时长在 open_media 里按
+        // Content-Length/比特率估算。HTTP 来源的时长始终标记为估计值，
+        // 之后读到更多数据也不会更新时长或这一标记。
+        let (reader, duration, file_name, estimated) = match open_media(&media.src) {
+            Some(t) => t,
+            None => return false,
+        };
+        self.append_reader(media.src, reader, file_name, duration, estimated, once)
     }
 
     // fn next(&mut self) -> bool {
@@ -156,18 +631,30 @@ impl Player for MusicPlayer {
 
     fn play(&mut self) -> bool {
         self._sink.play();
+        let mut started = None;
+        let mut resumed = false;
         if let Some(item) = self.play_list.lists.first_mut() {
             let status = &mut item.status;
             match status {
                 PlayStatus::Waiting => {
                     *status = PlayStatus::Playing(Instant::now(), Duration::from_nanos(0));
+                    started = Some(item.name.clone());
                 }
                 PlayStatus::Playing(_, _) => {}
                 PlayStatus::Stopped(duration) => {
                     *status = PlayStatus::Playing(Instant::now(), *duration);
+                    resumed = true;
                 }
             }
         }
+        if let Some(name) = started {
+            self.emit(PlayerEvent::TrackStarted(name));
+        }
+        // play() 早于 resume() 存在，至今仍兼作“取消暂停”用，同一状态迁移要
+        // 发出同样的事件，否则只靠事件流而不轮询的订阅者会漏掉这次恢复播放。
+        if resumed {
+            self.emit(PlayerEvent::Resumed);
+        }
         true
     }
 
@@ -188,6 +675,7 @@ impl Player for MusicPlayer {
                 PlayStatus::Stopped(_) => {}
             }
         }
+        self.emit(PlayerEvent::Paused);
         true
     }
 
@@ -203,6 +691,7 @@ impl Player for MusicPlayer {
                 }
             }
         }
+        self.emit(PlayerEvent::Resumed);
         return true;
     }
 
@@ -210,12 +699,56 @@ impl Player for MusicPlayer {
         return self.initialized && !self._sink.is_paused() && !self.play_list.lists.is_empty();
     }
 
+    fn seek(&mut self, pos: Duration) -> bool {
+        // Sink 无法对已经 append 的源做 seek，因此用缓冲源的廉价克隆重建 Sink
+        let source = match &self.current_source {
+            Some(s) => s.clone(),
+            None => return false,
+        };
+        let volume = self._sink.volume();
+        let paused = self._sink.is_paused();
+        self._sink.stop();
+        self._sink = Sink::try_new(&self._stream_handle).unwrap();
+        self._sink.set_volume(volume);
+        self._sink.append(source.skip_duration(pos));
+        if paused {
+            self._sink.pause();
+        } else {
+            self._sink.play();
+        }
+        // 重置播放头，让 tick 里的 start_position + elapsed 计算保持正确；
+        // 暂停中的 seek 记为 Stopped(pos)，与 pause() 一致，播放头不随时间走动
+        if let Some(item) = self.play_list.lists.first_mut() {
+            item.current_pos = pos;
+            item.status = if paused {
+                PlayStatus::Stopped(pos)
+            } else {
+                PlayStatus::Playing(Instant::now(), pos)
+            };
+        }
+        self.current_time = pos;
+        true
+    }
+
     fn get_progress(&self) -> (f32, f32) {
-        return (0.0, 0.0);
+        if let Some(item) = self.play_list.lists.first() {
+            let current = item.playhead().min(item.duration);
+            return (current.as_secs_f32(), item.duration.as_secs_f32());
+        }
+        (0.0, 0.0)
     }
 
     fn tick(&mut self) {
+        // 收取后台线程已经解码好的预加载源
+        if let Some(rx) = &self.preload_rx {
+            if let Ok(ready) = rx.try_recv() {
+                self.preload = Some(ready);
+                self.preload_rx = None;
+            }
+        }
         let is_playing = self.is_playing();
+        // 播放头变化事件在释放列表借用后再派发
+        let mut position = None;
         if let Some(song) = self.play_list.lists.first_mut() {
             let status = &mut song.status;
             match status {
@@ -225,14 +758,35 @@ impl Player for MusicPlayer {
                     }
                 }
                 PlayStatus::Playing(instant, duration) => {
-                    let now = instant.elapsed().add(duration.clone());
-                    if now.ge(&song.duration) {
-                        // next song, delete 0
-                        self.play_list.lists.remove(0);
+                    let now = instant.elapsed().add(*duration);
+                    let total = song.duration;
+                    let estimated = song.duration_estimated;
+                    // 时长是估计值（尤其是直播流的 0）时不能用 wall-clock 比较，
+                    // 改以 Sink 是否排空来判断这首是否真正放完。
+                    let finished = if estimated {
+                        self._sink.empty()
+                    } else {
+                        now.ge(&total)
+                    };
+                    if finished {
+                        // 按播放模式决定下一首
+                        self.advance_after_finish();
+                    } else if total.saturating_sub(now) < PRELOAD_THRESHOLD {
+                        // 快结束了，提前在后台解码下一首。`open_media` 对所有
+                        // HTTP 来源都固定把 estimated 设为 true（:118），若在
+                        // 这里也拿它当预加载的开关，正在播的这首只要是 HTTP
+                        // 源就永远不会触发预加载——就算时长估计是准的也一样。
+                        // 结束判断仍然看 estimated（时长可能只是个粗略估计），
+                        // 但预加载只看剩余时间，不看这首自己的时长是不是估计值。
+                        self.current_time = now;
+                        self.total_time = total;
+                        position = Some(now);
+                        self.preload_next();
                     } else {
                         // update status
                         self.current_time = now;
-                        self.total_time = song.duration.clone();
+                        self.total_time = total;
+                        position = Some(now);
                     }
                 }
                 PlayStatus::Stopped(dur) => {
@@ -246,19 +800,37 @@ impl Player for MusicPlayer {
                 self.stop();
             }
         }
+        if let Some(now) = position {
+            self.emit(PlayerEvent::PositionChanged(now));
+        }
     }
 
     fn next(&mut self) -> bool {
         let len = self.play_list.lists.len();
-        if len > 1 {
-            self.stop();
-            self.play_list.lists.remove(0);
-            // for
-        } else {
-            // no more sound to play
-            return false;
+        match self.play_list.mode {
+            // 列表循环/随机：即使只剩一首也能继续（循环回来或重新随机）
+            PlayMode::RepeatAll | PlayMode::Shuffle => {
+                if len == 0 {
+                    return false;
+                }
+                self.stop();
+                self.advance_after_finish();
+                true
+            }
+            // 顺序/单曲循环：手动下一首按顺序跳，末尾则停止
+            PlayMode::Sequential | PlayMode::RepeatOne => {
+                if len > 1 {
+                    self.play_list.lists.remove(0);
+                    // restart_front 会停掉旧 Sink、接上新源并开始播放，
+                    // 避免只更新 current_source 却不 append 导致的静音。
+                    self.restart_front(false);
+                } else {
+                    // no more sound to play
+                    return false;
+                }
+                true
+            }
         }
-        true
     }
 }
 
@@ -269,13 +841,314 @@ impl MusicPlayer {
 
     pub fn set_volume(&mut self, new_volume: f32) -> bool {
         self._sink.set_volume(new_volume);
+        self.emit(PlayerEvent::VolumeChanged(new_volume));
         true
     }
 
+    // 订阅播放状态变化，返回一个接收端；可多次调用以支持多个消费者。
+    pub fn subscribe(&mut self) -> Receiver<PlayerEvent> {
+        let (tx, rx) = mpsc::channel();
+        self.subscribers.push(tx);
+        rx
+    }
+
+    // 向所有订阅者广播事件，顺手清理已经断开的接收端。
+    fn emit(&mut self, event: PlayerEvent) {
+        self.subscribers
+            .retain(|tx| tx.send(event.clone()).is_ok());
+    }
+
     pub fn playing_song(&self) -> Option<&PlayListItem> {
         return self.play_list.lists.first();
     }
 
+    // 枚举当前 host 上可用的输出设备名称。
+    pub fn list_output_devices() -> Vec<String> {
+        use rodio::cpal::traits::{DeviceTrait, HostTrait};
+        let host = rodio::cpal::default_host();
+        host.output_devices()
+            .map(|devices| devices.filter_map(|d| d.name().ok()).collect())
+            .unwrap_or_default()
+    }
+
+    // 切换输出设备：在指定 cpal 设备上重建 stream/handle/sink，并把当前曲目
+    // 从缓冲克隆在当前播放头处重新接上，音频不中断。若设备打不开则保留原设备并返回 false。
+    pub fn set_output_device(&mut self, name: &str) -> bool {
+        use rodio::cpal::traits::{DeviceTrait, HostTrait};
+        let host = rodio::cpal::default_host();
+        let device = match host.output_devices() {
+            Ok(mut devices) => devices.find(|d| d.name().map(|n| n == name).unwrap_or(false)),
+            Err(_) => None,
+        };
+        let device = match device {
+            Some(d) => d,
+            None => return false,
+        };
+        let (stream, stream_handle) = match OutputStream::try_from_device(&device) {
+            Ok(pair) => pair,
+            Err(_) => return false,
+        };
+        let sink = match Sink::try_new(&stream_handle) {
+            Ok(s) => s,
+            Err(_) => return false,
+        };
+        // 保留音量与暂停状态
+        let volume = self._sink.volume();
+        let paused = self._sink.is_paused();
+        sink.set_volume(volume);
+        // 当前播放头：status 不变，沿用 start_position + elapsed 模型
+        let pos = match self.play_list.lists.first() {
+            Some(item) => match &item.status {
+                PlayStatus::Playing(instant, start) => instant.elapsed().add(*start),
+                PlayStatus::Stopped(dur) => *dur,
+                PlayStatus::Waiting => Duration::from_secs(0),
+            },
+            None => Duration::from_secs(0),
+        };
+        if let Some(source) = &self.current_source {
+            sink.append(source.clone().skip_duration(pos));
+        }
+        if paused {
+            sink.pause();
+        } else {
+            sink.play();
+        }
+        // 替换旧的 stream/handle/sink（旧的在此处被 drop，停掉原设备上的播放）
+        self._stream = stream;
+        self._stream_handle = stream_handle;
+        self._sink = sink;
+        true
+    }
+
+    // 设置播放模式，切换到随机模式时清空历史记录以便重新洗牌。
+    pub fn set_play_mode(&mut self, mode: PlayMode) {
+        if mode == PlayMode::Shuffle {
+            self.play_list.played.clear();
+        }
+        self.play_list.mode = mode;
+    }
+
+    // 循环切换播放模式，返回切换后的模式。
+    pub fn cycle_play_mode(&mut self) -> PlayMode {
+        let next = self.play_list.mode.next();
+        self.set_play_mode(next);
+        next
+    }
+
+    pub fn play_mode(&self) -> PlayMode {
+        self.play_list.mode
+    }
+
+    // 解码任意可随机访问的字节源并加入播放列表；仅当它成为当前曲目（列表原本为空）
+    // 时才把缓冲源追加到 Sink，并保留一份缓冲克隆用于 seek。
+    fn append_reader(
+        &mut self,
+        src: super::media::Source,
+        reader: Box<dyn ReadSeek>,
+        file_name: String,
+        duration: Duration,
+        estimated: bool,
+        once: bool,
+    ) -> bool {
+        let buf_reader = BufReader::new(reader);
+        let dec = match Decoder::new(buf_reader) {
+            Ok(dec) => dec,
+            Err(_) => return false,
+        };
+        if !self.initialized {
+            self.initialized = true;
+            self.start_listening_thread();
+        }
+        if once {
+            self._sink.stop();
+            self._sink = Sink::try_new(&self._stream_handle).unwrap();
+            self.play_list.lists.clear();
+        }
+        // 空列表时这首就是当前曲目，立即接入 Sink 并记为当前源；否则只入队，
+        // 等前面的曲目放完由 advance 消费预加载源，不做多余的急切 append。
+        let is_current = self.play_list.lists.is_empty();
+        // 缓冲源内部用 Arc 保存已解码样本，克隆代价极低
+        let source: BufferedSource = dec.buffered();
+        if is_current {
+            self._sink.append(source.clone());
+            self.current_source = Some(source);
+        }
+        // add to playlist
+        let id = self.next_item_id;
+        self.next_item_id += 1;
+        self.play_list.lists.push(PlayListItem {
+            id,
+            name: file_name,
+            duration,
+            current_pos: Duration::from_secs(0),
+            status: PlayStatus::Waiting,
+            src,
+            duration_estimated: estimated,
+        });
+        if is_current {
+            // start play
+            self.play();
+            // manually tick once
+            self.tick();
+        }
+        true
+    }
+
+    // 按当前播放模式推断下一首将要播放的曲目下标。随机模式下的下一首要到切歌
+    // 时才随机确定，无法提前命中，故返回 None（跳过预加载）。
+    fn peek_next_index(&self) -> Option<usize> {
+        peek_next_index_for(self.play_list.mode, self.play_list.lists.len())
+    }
+
+    // 在后台线程解码下一首，解码结果通过 channel 回传，tick 里轮询取用。
+    fn preload_next(&mut self) {
+        // 已经有预加载结果或正在预加载时不重复触发
+        if self.preload.is_some() || self.preload_rx.is_some() {
+            return;
+        }
+        // RepeatOne、以及只剩一首的 RepeatAll 播完后都会走 restart_front(true)
+        // 直接复用 current_source，这两种情况下预加载的结果永远不会被消费——
+        // 对 HTTP 源来说还会白白开一条新连接和一条新的 prefetch 后台线程。
+        let len = self.play_list.lists.len();
+        let reuses_current = matches!(self.play_list.mode, PlayMode::RepeatOne)
+            || (self.play_list.mode == PlayMode::RepeatAll && len <= 1);
+        if reuses_current {
+            return;
+        }
+        let next = match self.peek_next_index().and_then(|i| self.play_list.lists.get(i)) {
+            Some(item) => item,
+            None => return,
+        };
+        let src = clone_source(&next.src);
+        let id = next.id;
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            if let Some((reader, _duration, _name, _estimated)) = open_media(&src) {
+                if let Ok(dec) = Decoder::new(BufReader::new(reader)) {
+                    let _ = tx.send((id, dec.buffered()));
+                }
+            }
+        });
+        self.preload_rx = Some(rx);
+    }
+
+    // 当前曲目播完后，按播放模式决定下一步。
+    fn advance_after_finish(&mut self) {
+        let finished = self.play_list.lists.first().map(|item| item.name.clone());
+        match self.play_list.mode {
+            PlayMode::RepeatOne => {
+                // 单曲循环：还是同一首，复用已缓冲的当前源重新从头播放，不改动列表
+                self.restart_front(true);
+            }
+            PlayMode::Sequential => {
+                self.play_list.lists.remove(0);
+                // 消费预加载好的源并真正接入 Sink（不再依赖急切 append 的队列副本）
+                self.restart_front(false);
+            }
+            PlayMode::RepeatAll => {
+                // 列表循环：把播完的曲目挪到队尾而不是丢弃
+                let single = self.play_list.lists.len() <= 1;
+                if !self.play_list.lists.is_empty() {
+                    let item = self.play_list.lists.remove(0);
+                    self.play_list.lists.push(item);
+                }
+                // 整张只有一首时挪到队尾又回到队首，还是同一首，复用当前源
+                self.restart_front(single);
+            }
+            PlayMode::Shuffle => {
+                if let Some(finished) = self.play_list.lists.first() {
+                    self.play_list.played.push(finished.id);
+                }
+                match self.pick_shuffle_index() {
+                    Some(idx) => {
+                        let item = self.play_list.lists.remove(idx);
+                        self.play_list.lists.insert(0, item);
+                        self.restart_front(false);
+                    }
+                    None => {
+                        // 没有可播的曲目了
+                        self.play_list.lists.clear();
+                        self.current_source = None;
+                    }
+                }
+            }
+        }
+        self.preload = None;
+        self.preload_rx = None;
+        // 先通知旧曲目结束，再通知新曲目开始（或列表已空）
+        if let Some(name) = finished {
+            self.emit(PlayerEvent::TrackEnded(name));
+        }
+        match self.play_list.lists.first().map(|item| item.name.clone()) {
+            Some(name) => self.emit(PlayerEvent::TrackStarted(name)),
+            None => self.emit(PlayerEvent::PlaylistEmptied),
+        }
+    }
+
+    // 随机模式下挑选下一首：在尚未播放过的曲目里随机取，全部播完则重置记录。
+    fn pick_shuffle_index(&mut self) -> Option<usize> {
+        if self.play_list.lists.is_empty() {
+            return None;
+        }
+        let mut candidates = unplayed_indices(&self.play_list.lists, &self.play_list.played);
+        if candidates.is_empty() {
+            // 整张已经放完一轮，重新开始
+            self.play_list.played.clear();
+            candidates = (0..self.play_list.lists.len()).collect();
+        }
+        let mut rng = rand::thread_rng();
+        candidates.choose(&mut rng).copied()
+    }
+
+    // 用队首曲目缓冲源的克隆重建 Sink 并从头播放，同时刷新当前源与状态。
+    // reuse_current 为 true 时说明队首仍是刚播完的同一曲目（RepeatOne、单曲
+    // 列表循环），直接复用 current_source 的克隆重播，不走 adopt_front_source
+    // 重新 open_media/解码——对 Source::Http 而言这能省掉一次新开的 TCP 连接
+    // 和一条新的 prefetch 后台线程。
+    fn restart_front(&mut self, reuse_current: bool) {
+        if !reuse_current {
+            self.adopt_front_source();
+        }
+        let source = match &self.current_source {
+            Some(s) => s.clone(),
+            None => return,
+        };
+        let volume = self._sink.volume();
+        self._sink.stop();
+        self._sink = Sink::try_new(&self._stream_handle).unwrap();
+        self._sink.set_volume(volume);
+        self._sink.append(source);
+        self._sink.play();
+        if let Some(item) = self.play_list.lists.first_mut() {
+            item.current_pos = Duration::from_secs(0);
+            item.status = PlayStatus::Waiting;
+        }
+    }
+
+    // 把当前队首曲目的缓冲源设为 current_source（优先取用预加载结果）。
+    fn adopt_front_source(&mut self) {
+        if let Some(next) = self.play_list.lists.first() {
+            let id = next.id;
+            let src = clone_source(&next.src);
+            self.current_source = self.take_ready_source(id, &src);
+        } else {
+            self.current_source = None;
+        }
+    }
+
+    // 取用已经解码好的预加载源（若有），否则同步解码指定来源。按列表项 id
+    // 匹配，而不是文件名——同名曲目重复入队时文件名匹配会把别的曲目预加载
+    // 的缓冲源错发给它。
+    fn take_ready_source(&mut self, id: u64, src: &super::media::Source) -> Option<BufferedSource> {
+        if let Some((ready_id, source)) = self.preload.take() {
+            if ready_id == id {
+                return Some(source);
+            }
+        }
+        let (reader, _duration, _name, _estimated) = open_media(src)?;
+        Decoder::new(BufReader::new(reader)).ok().map(|d| d.buffered())
+    }
+
     fn start_listening_thread(&mut self) {
         // let mutex = Mutex::new(self);
         // let arc = Arc::new(mutex);
@@ -292,3 +1165,232 @@ impl Drop for MusicPlayer {
         // println!()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(id: u64, name: &str, status: PlayStatus) -> PlayListItem {
+        PlayListItem {
+            id,
+            name: name.to_string(),
+            duration: Duration::from_secs(10),
+            current_pos: Duration::from_secs(0),
+            status,
+            src: super::super::media::Source::Local(name.to_string()),
+            duration_estimated: false,
+        }
+    }
+
+    // 用预填充的 buffer 构造一个“已整体下载完成”的读取器，read/seek 不会触网。
+    fn stub_reader(bytes: Vec<u8>) -> HttpStreamReader {
+        let state = StreamState {
+            total_len: Some(bytes.len() as u64),
+            bitrate: Some(128 * 1000),
+            headers_ready: true,
+            complete: true,
+            errored: false,
+            buffer: bytes,
+        };
+        HttpStreamReader {
+            shared: Arc::new((Mutex::new(state), Condvar::new())),
+            pos: 0,
+        }
+    }
+
+    // 可配置的内存 fetcher，用来在不触网的情况下驱动 prefetch 的填充逻辑。
+    struct FakeFetcher {
+        data: Vec<u8>,
+        // 以 206 分块应答（真 Range 支持），否则以 200 返回整段 body
+        partial: bool,
+        // 是否声明 Content-Length（无限流声明为 None）
+        announce_len: bool,
+    }
+
+    impl RangeFetcher for FakeFetcher {
+        fn get(&self, start: u64, len: u64) -> io::Result<RangeResponse> {
+            if self.partial {
+                let start = start as usize;
+                let end = (start + len as usize).min(self.data.len());
+                let slice = self.data.get(start..end).unwrap_or(&[]).to_vec();
+                Ok(RangeResponse {
+                    partial: true,
+                    content_length: None,
+                    bitrate: Some(128 * 1000),
+                    body: Box::new(io::Cursor::new(slice)),
+                })
+            } else {
+                Ok(RangeResponse {
+                    partial: false,
+                    content_length: self.announce_len.then_some(self.data.len() as u64),
+                    bitrate: Some(128 * 1000),
+                    body: Box::new(io::Cursor::new(self.data.clone())),
+                })
+            }
+        }
+    }
+
+    fn run_prefetch(fetcher: Box<dyn RangeFetcher>, cap: u64) -> StreamState {
+        let shared: Arc<(Mutex<StreamState>, Condvar)> =
+            Arc::new((Mutex::new(StreamState::default()), Condvar::new()));
+        prefetch(&shared, fetcher.as_ref(), cap);
+        Arc::try_unwrap(shared).ok().unwrap().0.into_inner().unwrap()
+    }
+
+    #[test]
+    fn play_mode_cycles_through_all_modes() {
+        let mut mode = PlayMode::Sequential;
+        mode = mode.next();
+        assert_eq!(mode, PlayMode::RepeatOne);
+        mode = mode.next();
+        assert_eq!(mode, PlayMode::RepeatAll);
+        mode = mode.next();
+        assert_eq!(mode, PlayMode::Shuffle);
+        mode = mode.next();
+        assert_eq!(mode, PlayMode::Sequential);
+    }
+
+    #[test]
+    fn playhead_tracks_start_offset() {
+        assert_eq!(item(0, "a", PlayStatus::Waiting).playhead(), Duration::ZERO);
+        assert_eq!(
+            item(0, "a", PlayStatus::Stopped(Duration::from_secs(7))).playhead(),
+            Duration::from_secs(7)
+        );
+        // Playing 从 3s 偏移开始，playhead 至少为该偏移
+        let playing = item(
+            0,
+            "a",
+            PlayStatus::Playing(Instant::now(), Duration::from_secs(3)),
+        );
+        let head = playing.playhead();
+        assert!(head >= Duration::from_secs(3));
+        assert!(head < Duration::from_secs(4));
+    }
+
+    #[test]
+    fn peek_next_index_matches_mode_and_list_length() {
+        // 单曲循环：无论列表长度，下一首都还是队首自己
+        assert_eq!(peek_next_index_for(PlayMode::RepeatOne, 1), Some(0));
+        assert_eq!(peek_next_index_for(PlayMode::RepeatOne, 3), Some(0));
+        // 顺序播放：只有一首时没有下一首可预加载，多首时是下标 1
+        assert_eq!(peek_next_index_for(PlayMode::Sequential, 1), None);
+        assert_eq!(peek_next_index_for(PlayMode::Sequential, 3), Some(1));
+        // 列表循环：只有一首时回到自己，多首时是下标 1
+        assert_eq!(peek_next_index_for(PlayMode::RepeatAll, 1), Some(0));
+        assert_eq!(peek_next_index_for(PlayMode::RepeatAll, 3), Some(1));
+        // 随机模式：下一首要到切歌时才随机确定，任何长度都不提前命中
+        assert_eq!(peek_next_index_for(PlayMode::Shuffle, 1), None);
+        assert_eq!(peek_next_index_for(PlayMode::Shuffle, 3), None);
+        // 空列表任何模式下都没有下一首
+        assert_eq!(peek_next_index_for(PlayMode::Sequential, 0), None);
+        assert_eq!(peek_next_index_for(PlayMode::RepeatOne, 0), None);
+        assert_eq!(peek_next_index_for(PlayMode::RepeatAll, 0), None);
+        assert_eq!(peek_next_index_for(PlayMode::Shuffle, 0), None);
+    }
+
+    #[test]
+    fn shuffle_cycles_whole_list_before_repeating() {
+        let lists = vec![
+            item(0, "a", PlayStatus::Waiting),
+            item(1, "b", PlayStatus::Waiting),
+            item(2, "c", PlayStatus::Waiting),
+        ];
+        // 没有播放记录时，全部都是候选
+        assert_eq!(unplayed_indices(&lists, &[]), vec![0, 1, 2]);
+        // 播放过的要被排除
+        assert_eq!(unplayed_indices(&lists, &[0]), vec![1, 2]);
+        // 整张放完后没有候选，调用方据此重置记录
+        assert!(unplayed_indices(&lists, &[0, 1, 2]).is_empty());
+    }
+
+    #[test]
+    fn unplayed_indices_by_id_survives_duplicate_names() {
+        // 两个不同专辑里同名的 01.mp3：按 id 而不是文件名区分，
+        // 其中一个放过了不应该连带把另一个也标记为已放过
+        let lists = vec![
+            item(0, "01.mp3", PlayStatus::Waiting),
+            item(1, "01.mp3", PlayStatus::Waiting),
+        ];
+        assert_eq!(unplayed_indices(&lists, &[0]), vec![1]);
+    }
+
+    #[test]
+    fn http_reader_assembles_bytes_on_read_and_seek() {
+        let data: Vec<u8> = (0..32u8).collect();
+        let mut reader = stub_reader(data.clone());
+        let mut buf = [0u8; 8];
+        assert_eq!(reader.read(&mut buf).unwrap(), 8);
+        assert_eq!(&buf, &data[0..8]);
+        // 顺序读继续前进
+        assert_eq!(reader.read(&mut buf).unwrap(), 8);
+        assert_eq!(&buf, &data[8..16]);
+        // 回跳后再读取应从新位置开始
+        assert_eq!(reader.seek(SeekFrom::Start(4)).unwrap(), 4);
+        assert_eq!(reader.read(&mut buf).unwrap(), 8);
+        assert_eq!(&buf, &data[4..12]);
+        // 读到末尾返回 0
+        reader.seek(SeekFrom::End(0)).unwrap();
+        assert_eq!(reader.read(&mut buf).unwrap(), 0);
+    }
+
+    #[test]
+    fn prefetch_assembles_chunked_partial_content() {
+        // 206 分块：应按顺序请求多段并拼成完整数据
+        let data: Vec<u8> = (0..200u8).collect();
+        let state = run_prefetch(
+            Box::new(FakeFetcher {
+                data: data.clone(),
+                partial: true,
+                announce_len: false,
+            }),
+            HTTP_STREAM_CAP,
+        );
+        assert_eq!(state.buffer, data);
+        assert!(state.complete);
+        assert!(!state.errored);
+    }
+
+    #[test]
+    fn prefetch_accepts_full_body_when_range_ignored() {
+        // 200 且有 Content-Length：整段 body 就是全文件，不应重复追加
+        let data: Vec<u8> = (0..200u8).collect();
+        let state = run_prefetch(
+            Box::new(FakeFetcher {
+                data: data.clone(),
+                partial: false,
+                announce_len: true,
+            }),
+            HTTP_STREAM_CAP,
+        );
+        assert_eq!(state.buffer, data);
+        assert_eq!(state.total_len, Some(data.len() as u64));
+        assert!(state.complete);
+    }
+
+    #[test]
+    fn prefetch_caps_endless_body() {
+        // 200 且无 Content-Length（直播流）：缓存到上限即停，不无界增长
+        let cap = 4096u64;
+        let state = run_prefetch(
+            Box::new(FakeFetcher {
+                data: vec![7u8; 64 * 1024],
+                partial: false,
+                announce_len: false,
+            }),
+            cap,
+        );
+        assert_eq!(state.total_len, None);
+        assert!(state.buffer.len() as u64 >= cap);
+        assert!(state.buffer.len() as u64 <= cap + 8192);
+        assert!(state.complete);
+    }
+
+    #[test]
+    fn estimate_duration_from_length_and_bitrate() {
+        // 16000 字节 * 8 / 128000 bit/s = 1 秒
+        let reader = stub_reader(vec![0u8; 16000]);
+        let est = reader.estimate_duration().unwrap();
+        assert!((est.as_secs_f64() - 1.0).abs() < 1e-6);
+    }
+}